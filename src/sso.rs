@@ -1,18 +1,25 @@
 use chrono::Utc;
-use rocket::http::CookieJar;
+use rocket::http::{Cookie, CookieJar, SameSite};
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::sync::Arc;
 use std::time::Duration;
 use url::Url;
 
-use jsonwebtoken::{DecodingKey, Validation};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
 use mini_moka::sync::Cache;
 use once_cell::sync::Lazy;
-use openidconnect::core::{CoreClient, CoreProviderMetadata, CoreResponseType, CoreUserInfoClaims};
+use openidconnect::core::{
+    CoreAuthDisplay, CoreClaimName, CoreClaimType, CoreClient, CoreClientAuthMethod, CoreGrantType, CoreJsonWebKey,
+    CoreJsonWebKeyType, CoreJsonWebKeyUse, CoreJweContentEncryptionAlgorithm, CoreJweKeyManagementAlgorithm,
+    CoreJwsSigningAlgorithm, CoreResponseMode, CoreResponseType, CoreSubjectIdentifierType,
+    CoreUserInfoClaims,
+};
 use openidconnect::reqwest::async_http_client;
 use openidconnect::{
-    AccessToken, AuthenticationFlow, AuthorizationCode, ClientId, ClientSecret, CsrfToken, IdToken, Nonce,
-    OAuth2TokenResponse, RefreshToken, Scope,
+    AccessToken, AdditionalProviderMetadata, AuthenticationFlow, AuthorizationCode, ClientId, ClientSecret, CsrfToken,
+    IdToken, IssuerUrl, Nonce, OAuth2TokenResponse, PkceCodeChallenge, PkceCodeVerifier, ProviderMetadata, RefreshToken,
+    Scope,
 };
 use regex::Regex;
 
@@ -22,61 +29,405 @@ use crate::{
     auth,
     auth::{AuthMethodScope, ClientIp},
     business::organization_logic,
-    db::models::{Device, EventType, Organization, SsoNonce, User, UserOrgType, UserOrganization},
+    db::models::{Collection, Device, EventType, Organization, User, UserOrgType, UserOrganization},
     db::DbConn,
     util::CustomRedirect,
     CONFIG,
 };
 
 pub static COOKIE_NAME_REDIRECT: Lazy<String> = Lazy::new(|| "sso_redirect_url".to_string());
+// Carries the signed, stateless login state built in `authorize_url` across the redirect to the IdP and back.
+static COOKIE_NAME_SSO_STATE: Lazy<String> = Lazy::new(|| "sso_state".to_string());
 pub static FAKE_IDENTIFIER: Lazy<String> = Lazy::new(|| "VaultWarden".to_string());
 
+// How long a login attempt has to complete the round-trip to the IdP before its state cookie expires.
+const SSO_STATE_TTL: i64 = 5 * 60;
+
 static AC_CACHE: Lazy<Cache<String, AuthenticatedUser>> =
     Lazy::new(|| Cache::builder().max_capacity(1000).time_to_live(Duration::from_secs(10 * 60)).build());
 
-static CLIENT_CACHE: RwLock<Option<CoreClient>> = RwLock::new(None);
+// One `CoreClient` (plus its discovered `jwks_uri`) per configured provider, discovered lazily and kept warm.
+static CLIENT_CACHE: Lazy<Cache<String, ProviderClient>> =
+    Lazy::new(|| Cache::builder().max_capacity(100).time_to_live(Duration::from_secs(60 * 60)).build());
+
+// The provider's signing keys, fetched from its `jwks_uri` and refreshed on a TTL or on an unknown `kid`.
+static JWKS_CACHE: Lazy<Cache<String, Arc<JwkSet>>> =
+    Lazy::new(|| Cache::builder().max_capacity(100).time_to_live(Duration::from_secs(15 * 60)).build());
 
-static SSO_JWT_VALIDATION: Lazy<Decoding> = Lazy::new(prepare_decoding);
+// Per-provider configuration, indexed by the `idp_id` the web vault and redirect routes use.
+static PROVIDERS: Lazy<HashMap<String, SsoProviderSettings>> = Lazy::new(load_providers);
+
+// Per-provider JWT decoding/validation state, built once the providers are known.
+static SSO_JWT_VALIDATION: Lazy<HashMap<String, Decoding>> =
+    Lazy::new(|| PROVIDERS.values().map(|provider| (provider.id.clone(), prepare_decoding(provider))).collect());
 
 static SSO_ERRORS_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^error_(.*)$").unwrap());
 
-// Will Panic if SSO is activated and a key file is present but we can't decode its content
+// Will Panic if SSO is activated and a provider config or key file is present but can't be decoded
 pub fn load_lazy() {
+    Lazy::force(&PROVIDERS);
     Lazy::force(&SSO_JWT_VALIDATION);
+    Lazy::force(&GROUP_MAPPINGS);
+}
+
+// Configuration for a single Identity Provider. `SSO_PROVIDERS` is a JSON array of these objects.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SsoProviderSettings {
+    pub id: String,
+    pub display_name: String,
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default = "default_roles_token_path")]
+    pub roles_token_path: String,
+    #[serde(default = "default_organizations_token_path")]
+    pub organizations_token_path: String,
+    // Optional static signing key override, kept per-provider since each IdP signs independently.
+    pub key_filepath: Option<String>,
+}
+
+fn default_roles_token_path() -> String {
+    "/resource_access/vaultwarden/roles".to_string()
+}
+
+fn default_organizations_token_path() -> String {
+    "/groups".to_string()
+}
+
+// Parse `CONFIG.sso_providers()` (a JSON array) into the provider map, falling back to the
+// legacy single-provider settings so existing single-IdP configs keep working unchanged.
+fn load_providers() -> HashMap<String, SsoProviderSettings> {
+    let raw = CONFIG.sso_providers();
+
+    let providers: Vec<SsoProviderSettings> = if raw.trim().is_empty() {
+        if CONFIG.sso_enabled() {
+            vec![SsoProviderSettings {
+                id: "default".to_string(),
+                display_name: "Single Sign-On".to_string(),
+                issuer_url: CONFIG.sso_issuer_url_raw(),
+                client_id: CONFIG.sso_client_id(),
+                client_secret: CONFIG.sso_client_secret(),
+                scopes: Vec::new(),
+                roles_token_path: CONFIG.sso_roles_token_path(),
+                organizations_token_path: CONFIG.sso_organizations_token_path(),
+                key_filepath: Some(CONFIG.sso_key_filepath()),
+            }]
+        } else {
+            Vec::new()
+        }
+    } else {
+        serde_json::from_str(&raw).unwrap_or_else(|e| {
+            panic!("Failed to parse `SSO_PROVIDERS`, expected a JSON array of provider objects: {e}");
+        })
+    };
+
+    providers.into_iter().map(|provider| (provider.id.clone(), provider)).collect()
 }
 
-// Call the OpenId discovery endpoint to retrieve configuration
-async fn get_client() -> ApiResult<CoreClient> {
-    let client_id = ClientId::new(CONFIG.sso_client_id());
-    let client_secret = ClientSecret::new(CONFIG.sso_client_secret());
+// Maps IdP group claims onto an organization, a `UserOrgType`, and an optional set of collections.
+// `pattern` is matched as a regex against each group name in the token (e.g. `^engineering-.*$`
+// or a plain literal for an exact match), so one mapping can cover a whole family of IdP groups.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SsoGroupMapping {
+    pub pattern: String,
+    pub organization: String,
+    #[serde(default = "default_user_type")]
+    pub user_type: String,
+    #[serde(default)]
+    pub collections: Vec<SsoCollectionMapping>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SsoCollectionMapping {
+    // Matched against `Collection::external_id`, not the (client-side-encrypted) collection name,
+    // since the server never sees collection names in plaintext.
+    pub external_id: String,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub hide_passwords: bool,
+}
 
-    let issuer_url = CONFIG.sso_issuer_url()?;
+fn default_user_type() -> String {
+    "user".to_string()
+}
+
+fn parse_user_org_type(value: &str) -> Option<UserOrgType> {
+    match value.to_lowercase().as_str() {
+        "owner" => Some(UserOrgType::Owner),
+        "admin" => Some(UserOrgType::Admin),
+        "manager" => Some(UserOrgType::Manager),
+        "user" => Some(UserOrgType::User),
+        _ => None,
+    }
+}
+
+// `UserOrgType`'s raw discriminant doesn't increase monotonically with privilege (`Manager` outranks
+// `User` despite a larger discriminant), so picking "the highest-privilege mapping" or deciding
+// promote-vs-demote has to go through actual rank, not a bare `as i32` cast.
+fn org_type_rank(atype: i32) -> i32 {
+    match atype {
+        x if x == UserOrgType::Owner as i32 => 3,
+        x if x == UserOrgType::Admin as i32 => 2,
+        x if x == UserOrgType::Manager as i32 => 1,
+        x if x == UserOrgType::User as i32 => 0,
+        _ => -1,
+    }
+}
+
+struct CompiledGroupMapping {
+    regex: Regex,
+    organization: String,
+    user_type: UserOrgType,
+    collections: Vec<SsoCollectionMapping>,
+}
+
+// Compiled once at startup: a bad regex or role name is a config error, not a per-login failure.
+static GROUP_MAPPINGS: Lazy<Vec<CompiledGroupMapping>> = Lazy::new(load_group_mappings);
+
+// The resolved grant for one organization once every matching `CompiledGroupMapping` has been
+// folded in: the highest-ranked `user_type` and the union of all their collections.
+struct WantedOrgGrant {
+    user_type: UserOrgType,
+    collections: Vec<SsoCollectionMapping>,
+}
 
-    let provider_metadata = match CoreProviderMetadata::discover_async(issuer_url, async_http_client).await {
-        Err(err) => err!(format!("Failed to discover OpenID provider: {err}")),
+// Pure (no DB, no async) so it can be exercised directly in tests: folds every `CompiledGroupMapping`
+// whose regex matches one of the user's groups into a per-organization grant, keeping the
+// highest-ranked `user_type` and the de-duplicated union of all matching mappings' collections.
+fn resolve_wanted_grants(groups: &[String], mappings: &[CompiledGroupMapping]) -> HashMap<String, WantedOrgGrant> {
+    let mut wanted: HashMap<String, WantedOrgGrant> = HashMap::new();
+
+    for group in groups {
+        for mapping in mappings {
+            if !mapping.regex.is_match(group) {
+                continue;
+            }
+
+            let grant = wanted.entry(mapping.organization.clone()).or_insert_with(|| WantedOrgGrant {
+                user_type: mapping.user_type,
+                collections: Vec::new(),
+            });
+
+            if org_type_rank(mapping.user_type as i32) > org_type_rank(grant.user_type as i32) {
+                grant.user_type = mapping.user_type;
+            }
+
+            for collection in &mapping.collections {
+                if !grant.collections.iter().any(|c| c.external_id == collection.external_id) {
+                    grant.collections.push(collection.clone());
+                }
+            }
+        }
+    }
+
+    wanted
+}
+
+fn load_group_mappings() -> Vec<CompiledGroupMapping> {
+    let raw = CONFIG.sso_group_mappings();
+    if raw.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let mappings: Vec<SsoGroupMapping> = serde_json::from_str(&raw).unwrap_or_else(|e| {
+        panic!("Failed to parse `SSO_GROUP_MAPPINGS`, expected a JSON array of group mapping objects: {e}");
+    });
+
+    mappings
+        .into_iter()
+        .map(|mapping| {
+            let regex = Regex::new(&mapping.pattern).unwrap_or_else(|e| {
+                panic!("Invalid SSO group mapping pattern `{}`: {e}", mapping.pattern);
+            });
+            let user_type = parse_user_org_type(&mapping.user_type).unwrap_or_else(|| {
+                panic!(
+                    "Invalid SSO group mapping user_type `{}` for pattern `{}`, expected one of: \
+                    owner, admin, manager, user",
+                    mapping.user_type, mapping.pattern
+                );
+            });
+
+            CompiledGroupMapping {
+                regex,
+                organization: mapping.organization,
+                user_type,
+                collections: mapping.collections,
+            }
+        })
+        .collect()
+}
+
+fn provider_config(idp_id: &str) -> ApiResult<&'static SsoProviderSettings> {
+    match PROVIDERS.get(idp_id) {
+        Some(provider) => Ok(provider),
+        None => err!(format!("Unknown SSO provider `{idp_id}`")),
+    }
+}
+
+fn decoding(idp_id: &str) -> ApiResult<&'static Decoding> {
+    match SSO_JWT_VALIDATION.get(idp_id) {
+        Some(decoding) => Ok(decoding),
+        None => err!(format!("No JWT validation configured for SSO provider `{idp_id}`")),
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SsoProviderInfo {
+    pub id: String,
+    pub display_name: String,
+}
+
+// List the configured providers, e.g. for the web vault to render a provider picker.
+pub fn providers() -> Vec<SsoProviderInfo> {
+    let mut providers: Vec<_> = PROVIDERS
+        .values()
+        .map(|provider| SsoProviderInfo {
+            id: provider.id.clone(),
+            display_name: provider.display_name.clone(),
+        })
+        .collect();
+    providers.sort_by(|a, b| a.id.cmp(&b.id));
+    providers
+}
+
+// A discovered client plus the `jwks_uri` advertised by the same discovery document, so we never
+// have to guess which key set backs a given `CoreClient`.
+#[derive(Clone)]
+struct ProviderClient {
+    client: CoreClient,
+    jwks_uri: Url,
+    end_session_endpoint: Option<Url>,
+}
+
+// `end_session_endpoint` is an RP-Initiated Logout extension, not part of the core discovery
+// document, so it's pulled in as additional provider metadata rather than being on `CoreProviderMetadata`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct EndSessionProviderMetadata {
+    end_session_endpoint: Option<String>,
+}
+
+impl AdditionalProviderMetadata for EndSessionProviderMetadata {}
+
+type SsoProviderMetadata = ProviderMetadata<
+    EndSessionProviderMetadata,
+    CoreAuthDisplay,
+    CoreClientAuthMethod,
+    CoreClaimName,
+    CoreClaimType,
+    CoreGrantType,
+    CoreJweContentEncryptionAlgorithm,
+    CoreJweKeyManagementAlgorithm,
+    CoreJwsSigningAlgorithm,
+    CoreJsonWebKeyType,
+    CoreJsonWebKeyUse,
+    CoreJsonWebKey,
+    CoreResponseMode,
+    CoreResponseType,
+    CoreSubjectIdentifierType,
+>;
+
+// Call the OpenId discovery endpoint to retrieve configuration for a specific provider
+async fn get_client(idp_id: &str) -> ApiResult<ProviderClient> {
+    let provider = provider_config(idp_id)?;
+
+    let client_id = ClientId::new(provider.client_id.clone());
+    let client_secret = ClientSecret::new(provider.client_secret.clone());
+
+    let issuer_url = match IssuerUrl::new(provider.issuer_url.clone()) {
+        Err(err) => err!(format!("Invalid issuer URL for SSO provider `{idp_id}`: {err}")),
+        Ok(issuer_url) => issuer_url,
+    };
+
+    let provider_metadata = match SsoProviderMetadata::discover_async(issuer_url, async_http_client).await {
+        Err(err) => err!(format!("Failed to discover OpenID provider `{idp_id}`: {err}")),
         Ok(metadata) => metadata,
     };
 
-    Ok(CoreClient::from_provider_metadata(provider_metadata, client_id, Some(client_secret))
-        .set_redirect_uri(CONFIG.sso_redirect_url()?))
+    let jwks_uri = provider_metadata.jwks_uri().url().clone();
+    let end_session_endpoint = provider_metadata
+        .additional_metadata()
+        .end_session_endpoint
+        .as_ref()
+        .and_then(|url| Url::parse(url).ok());
+
+    let client = CoreClient::from_provider_metadata(provider_metadata, client_id, Some(client_secret))
+        .set_redirect_uri(CONFIG.sso_redirect_url()?);
+
+    Ok(ProviderClient {
+        client,
+        jwks_uri,
+        end_session_endpoint,
+    })
 }
 
-// Simple cache to prevent recalling the discovery endpoint each time
-async fn cached_client() -> ApiResult<CoreClient> {
-    let cc_client = CLIENT_CACHE.read().ok().and_then(|rw_lock| rw_lock.clone());
-    match cc_client {
-        Some(client) => Ok(client),
-        None => get_client().await.map(|client| {
-            let mut cached_client = CLIENT_CACHE.write().unwrap();
-            *cached_client = Some(client.clone());
-            client
-        }),
+// Simple cache to prevent recalling the discovery endpoint each time, one entry per provider
+async fn cached_client(idp_id: &str) -> ApiResult<ProviderClient> {
+    if let Some(provider_client) = CLIENT_CACHE.get(idp_id) {
+        return Ok(provider_client);
     }
+
+    let provider_client = get_client(idp_id).await?;
+    CLIENT_CACHE.insert(idp_id.to_string(), provider_client.clone());
+    Ok(provider_client)
 }
 
-// The `nonce` allow to protect against replay attacks
-pub async fn authorize_url(mut conn: DbConn, state: String) -> ApiResult<Url> {
+// Fetch and cache a provider's JWK Set, keyed by provider id so key rotation on one IdP never
+// invalidates another's cache.
+async fn fetch_jwks(idp_id: &str, jwks_uri: &Url) -> ApiResult<Arc<JwkSet>> {
+    let response = match reqwest::get(jwks_uri.clone()).await {
+        Err(err) => err!(format!("Failed to fetch JWKS for SSO provider `{idp_id}`: {err}")),
+        Ok(response) => response,
+    };
+
+    let jwks: JwkSet = match response.json().await {
+        Err(err) => err!(format!("Failed to parse JWKS for SSO provider `{idp_id}`: {err}")),
+        Ok(jwks) => jwks,
+    };
+
+    let jwks = Arc::new(jwks);
+    JWKS_CACHE.insert(idp_id.to_string(), jwks.clone());
+    Ok(jwks)
+}
+
+// Resolve the JWK matching a token's `kid`. A single unknown `kid` triggers one refresh, to
+// survive routine key rotation without hammering the `jwks_uri` on every miss.
+async fn resolve_jwk(idp_id: &str, jwks_uri: &Url, kid: Option<&str>) -> ApiResult<Jwk> {
+    let jwks = match JWKS_CACHE.get(idp_id) {
+        Some(jwks) => jwks,
+        None => fetch_jwks(idp_id, jwks_uri).await?,
+    };
+
+    if let Some(jwk) = find_jwk(&jwks, kid) {
+        return Ok(jwk);
+    }
+
+    let jwks = fetch_jwks(idp_id, jwks_uri).await?;
+    match find_jwk(&jwks, kid) {
+        Some(jwk) => Ok(jwk),
+        None => err!(format!("No matching JWK for SSO provider `{idp_id}` (kid: {kid:?})")),
+    }
+}
+
+fn find_jwk(jwks: &JwkSet, kid: Option<&str>) -> Option<Jwk> {
+    match kid {
+        Some(kid) => jwks.find(kid).cloned(),
+        None => jwks.keys.first().cloned(),
+    }
+}
+
+// The `nonce` allows protecting against replay attacks. Rather than persisting it (and the PKCE
+// verifier) server-side, we pack them into a token signed with the server's own key and hand it
+// back to the browser as a short-lived cookie; `exchange_code` verifies and consumes it. This
+// trades a `SsoNonce` row (written on every login attempt, and never cleaned up if it's abandoned)
+// for a stateless round-trip through the browser.
+pub async fn authorize_url(jar: &CookieJar<'_>, state: String, idp_id: &str) -> ApiResult<Url> {
+    let provider = provider_config(idp_id)?;
+
     let mut scopes = vec![Scope::new("email".to_string()), Scope::new("profile".to_string())];
+    scopes.extend(provider.scopes.iter().cloned().map(Scope::new));
 
     if CONFIG.sso_organizations_invite() {
         if let Some(scope) = CONFIG.sso_organizations_scope() {
@@ -84,18 +435,38 @@ pub async fn authorize_url(mut conn: DbConn, state: String) -> ApiResult<Url> {
         }
     }
 
-    let (auth_url, _csrf_state, nonce) = cached_client()
+    // Protects the code exchange against interception, and is required by several public/enterprise IdPs.
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+    let saved_state = state.clone();
+    let (auth_url, _csrf_state, nonce) = cached_client(idp_id)
         .await?
+        .client
         .authorize_url(
             AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
             || CsrfToken::new(state),
             Nonce::new_random,
         )
         .add_scopes(scopes)
+        .set_pkce_challenge(pkce_challenge)
         .url();
 
-    let sso_nonce = SsoNonce::new(nonce.secret().to_string());
-    sso_nonce.save(&mut conn).await?;
+    let sso_state_token = auth::generate_sso_state_claims(
+        idp_id,
+        &saved_state,
+        nonce.secret(),
+        Some(pkce_verifier.secret().as_str()),
+        SSO_STATE_TTL,
+    );
+
+    jar.add(
+        Cookie::build((COOKIE_NAME_SSO_STATE.as_str(), sso_state_token))
+            .same_site(SameSite::Lax)
+            .http_only(true)
+            .secure(true)
+            .path("/")
+            .max_age(rocket::time::Duration::seconds(SSO_STATE_TTL)),
+    );
 
     Ok(auth_url)
 }
@@ -105,6 +476,8 @@ struct IdTokenPayload {
     exp: i64,
     email: Option<String>,
     nonce: String,
+    sub: String,
+    sid: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -136,8 +509,13 @@ pub enum UserRole {
 #[derive(Clone, Debug)]
 pub struct AuthenticatedUser {
     pub nonce: String,
+    pub idp_id: String,
     pub refresh_token: String,
     pub access_token: String,
+    // Kept as `id_token_hint` for RP-Initiated Logout, and to match against back-channel logout tokens.
+    pub id_token: String,
+    pub sso_sub: String,
+    pub sso_sid: Option<String>,
     pub email: String,
     pub user_name: Option<String>,
     pub role: Option<UserRole>,
@@ -150,32 +528,93 @@ impl AuthenticatedUser {
     }
 }
 
+// Per-provider JWT verification settings. The signing key itself is resolved per-token from
+// either the provider's JWKS (refreshed on rotation) or, if configured, a static file override.
 struct Decoding {
-    key: DecodingKey,
-    id_validation: Validation,
-    access_validation: Validation,
+    idp_id: String,
+    audience: String,
+    issuer: String,
+    static_key: Option<DecodingKey>,
     debug_key: DecodingKey,
     debug_validation: Validation,
 }
 
 impl Decoding {
-    pub fn new(key: DecodingKey, validation: Validation) -> Self {
-        let mut access_validation = validation.clone();
-        access_validation.validate_aud = false;
-
-        let mut debug_validation = insecure_validation();
+    pub fn new(idp_id: String, audience: String, issuer: String, static_key: Option<DecodingKey>) -> Self {
+        let mut debug_validation = insecure_validation(&audience);
         debug_validation.validate_aud = false;
 
         Decoding {
-            key,
-            id_validation: validation,
-            access_validation,
+            idp_id,
+            audience,
+            issuer,
+            static_key,
             debug_key: DecodingKey::from_secret(&[]),
             debug_validation,
         }
     }
 
-    pub fn id_token<
+    fn validation(&self, alg: Algorithm, validate_aud: bool) -> Validation {
+        let mut validation = Validation::new(alg);
+        validation.leeway = 30; // 30 seconds
+        validation.validate_exp = true;
+        validation.validate_nbf = true;
+        validation.validate_aud = validate_aud;
+        validation.set_audience(&[self.audience.clone()]);
+        validation.set_issuer(&[self.issuer.clone()]);
+        validation
+    }
+
+    // Resolve the key used to verify `token`. A configured static key override takes precedence
+    // (it's an explicit admin choice), otherwise fall back to the provider's JWKS so rotation and
+    // EC/EdDSA keys just work. If neither is available, fail rather than returning an empty key
+    // that would make every signature "valid".
+    async fn decoding_key(&self, jwks_uri: Option<&Url>, kid: Option<&str>) -> ApiResult<DecodingKey> {
+        if let Some(key) = &self.static_key {
+            return Ok(key.clone());
+        }
+
+        match jwks_uri {
+            Some(jwks_uri) => {
+                let jwk = resolve_jwk(&self.idp_id, jwks_uri, kid).await?;
+                match DecodingKey::from_jwk(&jwk) {
+                    Ok(key) => Ok(key),
+                    Err(err) => err!(format!("Invalid JWK for SSO provider `{}`: {err}", self.idp_id)),
+                }
+            }
+            None => err!(format!("No signing key available for SSO provider `{}`", self.idp_id)),
+        }
+    }
+
+    // Decode and verify a JWT, picking the algorithm and key from the token's own header rather
+    // than assuming a fixed algorithm, so provider key rotation and EC/EdDSA keys both work.
+    // There is no insecure fallback here: `decoding_key` errors out if neither a static key nor a
+    // JWKS is available, rather than silently skipping signature verification.
+    async fn decode<T: serde::de::DeserializeOwned>(
+        &self,
+        token_name: &str,
+        token: &str,
+        jwks_uri: Option<&Url>,
+        validate_aud: bool,
+    ) -> ApiResult<T> {
+        let header = match jsonwebtoken::decode_header(token) {
+            Err(err) => err!(format!("Could not decode {token_name} header: {err}")),
+            Ok(header) => header,
+        };
+
+        let key = self.decoding_key(jwks_uri, header.kid.as_deref()).await?;
+        let validation = self.validation(header.alg, validate_aud);
+
+        match jsonwebtoken::decode::<T>(token, &key, &validation) {
+            Ok(payload) => Ok(payload.claims),
+            Err(err) => {
+                self.log_debug(token_name, token);
+                err!(format!("Could not decode {token_name}: {err}"))
+            }
+        }
+    }
+
+    pub async fn id_token<
         AC: openidconnect::AdditionalClaims,
         GC: openidconnect::GenderClaim,
         JE: openidconnect::JweContentEncryptionAlgorithm<JT>,
@@ -183,6 +622,7 @@ impl Decoding {
         JT: openidconnect::JsonWebKeyType,
     >(
         &self,
+        jwks_uri: Option<&Url>,
         oic_id_token: Option<&IdToken<AC, GC, JE, JS, JT>>,
     ) -> ApiResult<IdTokenPayload> {
         let id_token_str = match oic_id_token {
@@ -190,18 +630,12 @@ impl Decoding {
             Some(token) => token.to_string(),
         };
 
-        match jsonwebtoken::decode::<IdTokenPayload>(id_token_str.as_str(), &self.key, &self.id_validation) {
-            Ok(payload) => Ok(payload.claims),
-            Err(err) => {
-                self.log_debug("identity_token", id_token_str.as_str());
-                err!(format!("Could not decode id token: {err}"))
-            }
-        }
+        self.decode("identity_token", id_token_str.as_str(), jwks_uri, true).await
     }
 
     // Errors are logged but will return None
-    fn roles(email: &str, token: &serde_json::Value) -> Option<UserRole> {
-        if let Some(json_roles) = token.pointer(&CONFIG.sso_roles_token_path()) {
+    fn roles(email: &str, roles_token_path: &str, token: &serde_json::Value) -> Option<UserRole> {
+        if let Some(json_roles) = token.pointer(roles_token_path) {
             match serde_json::from_value::<Vec<UserRole>>(json_roles.clone()) {
                 Ok(mut roles) => {
                     roles.sort();
@@ -219,8 +653,8 @@ impl Decoding {
     }
 
     // Errors are logged but will return an empty Vec
-    fn groups(email: &str, token: &serde_json::Value) -> Vec<String> {
-        if let Some(json_groups) = token.pointer(&CONFIG.sso_organizations_token_path()) {
+    fn groups(email: &str, organizations_token_path: &str, token: &serde_json::Value) -> Vec<String> {
+        if let Some(json_groups) = token.pointer(organizations_token_path) {
             match serde_json::from_value::<Vec<String>>(json_groups.clone()) {
                 Ok(groups) => groups,
                 Err(err) => {
@@ -234,7 +668,14 @@ impl Decoding {
         }
     }
 
-    fn access_token(&self, email: &str, access_token: &AccessToken) -> ApiResult<AccessTokenPayload> {
+    async fn access_token(
+        &self,
+        jwks_uri: Option<&Url>,
+        email: &str,
+        roles_token_path: &str,
+        organizations_token_path: &str,
+        access_token: &AccessToken,
+    ) -> ApiResult<AccessTokenPayload> {
         let mut role = None;
         let mut groups = Vec::new();
 
@@ -243,27 +684,24 @@ impl Decoding {
 
             self.log_debug("access_token", access_token_str);
 
-            match jsonwebtoken::decode::<serde_json::Value>(access_token_str, &self.key, &self.access_validation) {
-                Err(err) => err!(format!("Could not decode access token: {:?}", err)),
-                Ok(payload) => {
-                    if CONFIG.sso_roles_enabled() {
-                        role = Self::roles(email, &payload.claims);
-                        if !CONFIG.sso_roles_default_to_user() && role.is_none() {
-                            info!("User {email} failed to login due to missing/invalid role");
-                            err!(
-                                "Invalid user role. Contact your administrator",
-                                ErrorEvent {
-                                    event: EventType::UserFailedLogIn
-                                }
-                            )
-                        }
-                    }
+            let claims: serde_json::Value = self.decode("access_token", access_token_str, jwks_uri, false).await?;
 
-                    if CONFIG.sso_organizations_invite() {
-                        groups = Self::groups(email, &payload.claims);
-                    }
+            if CONFIG.sso_roles_enabled() {
+                role = Self::roles(email, roles_token_path, &claims);
+                if !CONFIG.sso_roles_default_to_user() && role.is_none() {
+                    info!("User {email} failed to login due to missing/invalid role");
+                    err!(
+                        "Invalid user role. Contact your administrator",
+                        ErrorEvent {
+                            event: EventType::UserFailedLogIn
+                        }
+                    )
                 }
             }
+
+            if CONFIG.sso_organizations_invite() {
+                groups = Self::groups(email, organizations_token_path, &claims);
+            }
         }
 
         Ok(AccessTokenPayload {
@@ -272,14 +710,13 @@ impl Decoding {
         })
     }
 
-    pub fn basic_token(&self, token_name: &str, token: &str) -> ApiResult<BasicTokenPayload> {
-        match jsonwebtoken::decode::<BasicTokenPayload>(token, &self.key, &self.access_validation) {
-            Ok(payload) => Ok(payload.claims),
-            Err(err) => {
-                self.log_debug(token_name, token);
-                err!(format!("Could not decode {token_name}: {err}"))
-            }
-        }
+    pub async fn basic_token(
+        &self,
+        jwks_uri: Option<&Url>,
+        token_name: &str,
+        token: &str,
+    ) -> ApiResult<BasicTokenPayload> {
+        self.decode(token_name, token, jwks_uri, false).await
     }
 
     pub fn log_debug(&self, token_name: &str, token: &str) {
@@ -288,46 +725,38 @@ impl Decoding {
     }
 }
 
-fn insecure_validation() -> Validation {
+fn insecure_validation(client_id: &str) -> Validation {
     let mut validation = jsonwebtoken::Validation::default();
-    validation.set_audience(&[CONFIG.sso_client_id()]);
+    validation.set_audience(&[client_id]);
     validation.insecure_disable_signature_validation();
 
     validation
 }
 
-// DecodingKey and Validation used to read the SSO JWT token response
-// If there is no key fallback to reading without validation
-fn prepare_decoding() -> Decoding {
-    let maybe_key = CONFIG.sso_enabled().then_some(()).and_then(|_| match std::fs::read(CONFIG.sso_key_filepath()) {
+// The provider's JWKS (resolved per-token via its `jwks_uri`) is the normal verification path.
+// The static key file is kept only as an optional override for providers that don't publish one.
+fn prepare_decoding(provider: &SsoProviderSettings) -> Decoding {
+    let static_key = provider.key_filepath.as_ref().and_then(|key_filepath| match std::fs::read(key_filepath) {
         Ok(key) => Some(DecodingKey::from_rsa_pem(&key).unwrap_or_else(|e| {
             panic!(
-                "Failed to decode optional SSO public RSA Key, format should exactly match:\n\
+                "Failed to decode optional SSO public RSA Key for provider `{}`, format should exactly match:\n\
                 -----BEGIN PUBLIC KEY-----\n\
                 ...\n\
                 -----END PUBLIC KEY-----\n\
-                Error: {e}"
+                Error: {e}",
+                provider.id
             );
         })),
         Err(err) => {
-            println!("[INFO] Can't read optional SSO public key at {} : {err}", CONFIG.sso_key_filepath());
+            println!(
+                "[INFO] Can't read optional SSO public key at {key_filepath} for provider `{}` : {err}",
+                provider.id
+            );
             None
         }
     });
 
-    match maybe_key {
-        Some(key) => {
-            let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256);
-            validation.leeway = 30; // 30 seconds
-            validation.validate_exp = true;
-            validation.validate_nbf = true;
-            validation.set_audience(&[CONFIG.sso_client_id()]);
-            validation.set_issuer(&[CONFIG.sso_authority()]);
-
-            Decoding::new(key, validation)
-        }
-        None => Decoding::new(DecodingKey::from_secret(&[]), insecure_validation()),
-    }
+    Decoding::new(provider.id.clone(), provider.client_id.clone(), provider.issuer_url.clone(), static_key)
 }
 
 #[derive(Clone, Debug)]
@@ -373,9 +802,17 @@ pub fn format_bitwarden_redirect(code: &str, state: &str, jar: &CookieJar<'_>) -
 // During the 2FA flow we will
 //  - retrieve the user information and then only discover he needs 2FA.
 //  - second time we will rely on the `AC_CACHE` since the `code` has already been exchanged.
-// The `nonce` will ensure that the user is authorized only once.
+// The signed `sso_state` cookie set by `authorize_url` is cleared from the browser as soon as we
+// read it; no record of it is kept server-side, so the token itself stays valid until it expires.
+// `AC_CACHE` (keyed on the one-time `code`) is what lets the second pass through the 2FA flow
+// succeed without the cookie still being present.
 // We return only the `UserInformation` to force calling `redeem` to obtain the `refresh_token`.
-pub async fn exchange_code(code: &String) -> ApiResult<UserInformation> {
+pub async fn exchange_code(
+    code: &String,
+    state: &str,
+    idp_id: &str,
+    jar: &CookieJar<'_>,
+) -> ApiResult<UserInformation> {
     match unwrap_sso_erors(code) {
         Some(Ok(auth::SSOCodeErrorClaims {
             error,
@@ -396,13 +833,42 @@ pub async fn exchange_code(code: &String) -> ApiResult<UserInformation> {
         });
     }
 
+    let provider = provider_config(idp_id)?;
+    let jwt_validation = decoding(idp_id)?;
+
+    let Some(sso_state_cookie) = jar.get(&COOKIE_NAME_SSO_STATE.to_string()) else {
+        err!("Failed to retrieve the SSO login state, it may have expired")
+    };
+    let sso_state = auth::decode_sso_state_claims(sso_state_cookie.value())?;
+    jar.remove(Cookie::from(COOKIE_NAME_SSO_STATE.to_string()));
+
+    if sso_state.idp_id != idp_id || sso_state.state != state {
+        err!("SSO login state does not match the callback, possible CSRF attempt")
+    }
+
+    let pkce_verifier = sso_state.verifier.map(PkceCodeVerifier::new);
+
     let oidc_code = AuthorizationCode::new(code.clone());
-    let client = cached_client().await?;
+    let provider_client = cached_client(idp_id).await?;
+    let client = &provider_client.client;
+    let jwks_uri = Some(&provider_client.jwks_uri);
 
-    match client.exchange_code(oidc_code).request_async(async_http_client).await {
+    let mut token_request = client.exchange_code(oidc_code);
+    if let Some(pkce_verifier) = pkce_verifier {
+        token_request = token_request.set_pkce_verifier(pkce_verifier);
+    }
+
+    match token_request.request_async(async_http_client).await {
         Ok(token_response) => {
-            let id_token = SSO_JWT_VALIDATION.id_token(token_response.extra_fields().id_token())?;
-            let user_info = retrieve_user_info(&client, token_response.access_token().to_owned()).await?;
+            let oic_id_token = token_response.extra_fields().id_token();
+            let id_token_str = oic_id_token.map(|token| token.to_string()).unwrap_or_default();
+            let id_token = jwt_validation.id_token(jwks_uri, oic_id_token).await?;
+
+            if id_token.nonce != sso_state.nonce {
+                err!("SSO nonce mismatch, possible replay attack")
+            }
+
+            let user_info = retrieve_user_info(client, token_response.access_token().to_owned()).await?;
             let user_name = user_info.preferred_username().map(|un| un.to_string());
 
             let email = match id_token.email {
@@ -413,7 +879,15 @@ pub async fn exchange_code(code: &String) -> ApiResult<UserInformation> {
                 },
             };
 
-            let access_token = SSO_JWT_VALIDATION.access_token(&email, token_response.access_token())?;
+            let access_token = jwt_validation
+                .access_token(
+                    jwks_uri,
+                    &email,
+                    &provider.roles_token_path,
+                    &provider.organizations_token_path,
+                    token_response.access_token(),
+                )
+                .await?;
 
             let refresh_token = match token_response.refresh_token() {
                 Some(token) => token.secret().to_string(),
@@ -422,8 +896,12 @@ pub async fn exchange_code(code: &String) -> ApiResult<UserInformation> {
 
             let authenticated_user = AuthenticatedUser {
                 nonce: id_token.nonce,
+                idp_id: idp_id.to_string(),
                 refresh_token,
                 access_token: token_response.access_token().secret().to_string(),
+                id_token: id_token_str,
+                sso_sub: id_token.sub,
+                sso_sid: id_token.sid,
                 email: email.clone(),
                 user_name: user_name.clone(),
                 role: access_token.role,
@@ -441,32 +919,56 @@ pub async fn exchange_code(code: &String) -> ApiResult<UserInformation> {
     }
 }
 
-// User has passed 2FA flow we can delete `nonce` and clear the cache.
-pub async fn redeem(code: &String, conn: &mut DbConn) -> ApiResult<AuthenticatedUser> {
+// User has passed 2FA flow, so we can clear the cache. The nonce itself was already verified
+// against the signed state cookie in `exchange_code`, and no login state is stored server-side,
+// so there's nothing left to clean up.
+pub async fn redeem(code: &String) -> ApiResult<AuthenticatedUser> {
     if let Some(au) = AC_CACHE.get(code) {
         AC_CACHE.invalidate(code);
-
-        if let Some(sso_nonce) = SsoNonce::find(&au.nonce, conn).await {
-            match sso_nonce.delete(conn).await {
-                Err(msg) => err!(format!("Failed to delete nonce: {msg}")),
-                Ok(_) => Ok(au),
-            }
-        } else {
-            err!("Failed to retrive nonce from db")
-        }
+        Ok(au)
     } else {
         err!("Failed to retrieve user info from sso cache")
     }
 }
 
-pub fn create_auth_tokens(
-    device: &Device,
+#[allow(clippy::too_many_arguments)]
+pub async fn create_auth_tokens(
+    device: &mut Device,
     user: &User,
+    idp_id: &str,
     refresh_token: String,
     access_token: &str,
+    id_token: Option<String>,
+    sso_sub: Option<String>,
+    sso_sid: Option<String>,
+    conn: &mut DbConn,
 ) -> ApiResult<auth::AuthTokens> {
-    let refresh_payload = SSO_JWT_VALIDATION.basic_token("refresh_token", &refresh_token)?;
-    let access_payload = SSO_JWT_VALIDATION.basic_token("access_token", access_token)?;
+    if let Some(sub) = &sso_sub {
+        if is_revoked(idp_id, sub, sso_sid.as_deref()) {
+            err!("SSO session was revoked by the identity provider, please log in again");
+        }
+    }
+
+    // Tag the device with the SSO subject/session-id backing this token, so a back-channel logout
+    // can find and clear every `Device` row tied to that IdP session, not just note it in the cache.
+    device.sso_sub.clone_from(&sso_sub);
+    device.sso_sid.clone_from(&sso_sid);
+    device.save(conn).await?;
+
+    let jwt_validation = decoding(idp_id)?;
+
+    // A configured static key doesn't need discovery to still have succeeded, so only a
+    // transient JWKS/discovery failure is tolerated when there's a static key to fall back on;
+    // otherwise it's a hard error rather than a silent `jwks_uri = None` that would otherwise
+    // leave nothing to verify the token's signature against.
+    let jwks_uri = if jwt_validation.static_key.is_some() {
+        cached_client(idp_id).await.ok().map(|provider_client| provider_client.jwks_uri)
+    } else {
+        Some(cached_client(idp_id).await?.jwks_uri)
+    };
+
+    let refresh_payload = jwt_validation.basic_token(jwks_uri.as_ref(), "refresh_token", &refresh_token).await?;
+    let access_payload = jwt_validation.basic_token(jwks_uri.as_ref(), "access_token", access_token).await?;
 
     debug!("Refresh_payload: {:?}", refresh_payload);
     debug!("Access_payload: {:?}", access_payload);
@@ -478,6 +980,10 @@ pub fn create_auth_tokens(
         sub: auth::AuthMethod::Sso,
         device_token: device.refresh_token.clone(),
         refresh_token: Some(refresh_token),
+        idp_id: Some(idp_id.to_string()),
+        id_token,
+        sso_sub,
+        sso_sid,
     };
 
     let access_claims = auth::LoginJwtClaims::new(
@@ -495,16 +1001,24 @@ pub fn create_auth_tokens(
 }
 
 pub async fn exchange_refresh_token(
-    device: &Device,
+    device: &mut Device,
     user: &User,
     refresh_claims: &auth::RefreshJwtClaims,
+    conn: &mut DbConn,
 ) -> ApiResult<auth::AuthTokens> {
+    let idp_id = refresh_claims.idp_id.as_deref().unwrap_or("default");
+
     if let Some(refresh_token) = &refresh_claims.refresh_token {
         let rt = RefreshToken::new(refresh_token.to_string());
 
-        let client = cached_client().await?;
+        let provider_client = cached_client(idp_id).await?;
 
-        let token_response = match client.exchange_refresh_token(&rt).request_async(async_http_client).await {
+        let token_response = match provider_client
+            .client
+            .exchange_refresh_token(&rt)
+            .request_async(async_http_client)
+            .await
+        {
             Err(err) => err!(format!("Request to exchange_refresh_token endpoint failed: {:?}", err)),
             Ok(token_response) => token_response,
         };
@@ -513,47 +1027,416 @@ pub async fn exchange_refresh_token(
         let rolled_refresh_token =
             token_response.refresh_token().map(|token| token.secret().to_string()).unwrap_or(refresh_token.to_string());
 
-        create_auth_tokens(device, user, rolled_refresh_token, token_response.access_token().secret())
+        // The refresh grant doesn't hand back a new id_token, so carry the original logout material forward.
+        create_auth_tokens(
+            device,
+            user,
+            idp_id,
+            rolled_refresh_token,
+            token_response.access_token().secret(),
+            refresh_claims.id_token.clone(),
+            refresh_claims.sso_sub.clone(),
+            refresh_claims.sso_sid.clone(),
+            conn,
+        )
+        .await
     } else {
         err!("Impossible to retrieve new access token, refresh_token is missing")
     }
 }
 
+// One entry per organization membership change made by `sync_groups`, so the login path can log
+// them as `EventType` entries without having to re-derive what happened.
+#[derive(Clone, Debug, Default)]
+pub struct GroupSyncSummary {
+    pub changes: Vec<(EventType, String)>,
+}
+
+// Resolve a mapping's target collections within `org` by `external_id`, skipping (and logging)
+// any that don't exist yet rather than failing the whole login. Matching is on `external_id`, not
+// `Collection::name` -- the name is client-side-encrypted and never visible to the server, so it
+// can never equal an admin-configured plaintext value.
+async fn resolve_collections(
+    org: &Organization,
+    mappings: &[SsoCollectionMapping],
+    conn: &mut DbConn,
+) -> Vec<CollectionData> {
+    let org_collections = Collection::find_by_organization(&org.uuid, conn).await;
+
+    mappings
+        .iter()
+        .filter_map(|mapping| {
+            let found = org_collections
+                .iter()
+                .find(|collection| collection.external_id.as_deref() == Some(mapping.external_id.as_str()));
+
+            match found {
+                Some(collection) => Some(CollectionData {
+                    id: collection.uuid.clone(),
+                    read_only: mapping.read_only,
+                    hide_passwords: mapping.hide_passwords,
+                }),
+                None => {
+                    warn!(
+                        "SSO group mapping references unknown collection external_id `{}` in organization `{}`",
+                        mapping.external_id, org.name
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
 pub async fn sync_groups(
     user: &User,
     device: &Device,
     ip: &ClientIp,
     groups: &Vec<String>,
     conn: &mut DbConn,
-) -> ApiResult<()> {
-    if CONFIG.sso_organizations_invite() {
-        let db_user_orgs = UserOrganization::find_any_state_by_user(&user.uuid, conn).await;
-        let user_orgs = db_user_orgs.iter().map(|uo| (uo.org_uuid.clone(), uo)).collect::<HashMap<_, _>>();
+) -> ApiResult<GroupSyncSummary> {
+    let mut summary = GroupSyncSummary::default();
+
+    if !CONFIG.sso_organizations_invite() {
+        return Ok(summary);
+    }
 
-        let org_groups: Vec<String> = vec![];
-        let org_collections: Vec<CollectionData> = vec![];
+    let db_user_orgs = UserOrganization::find_any_state_by_user(&user.uuid, conn).await;
+    let mut user_orgs = db_user_orgs.into_iter().map(|uo| (uo.org_uuid.clone(), uo)).collect::<HashMap<_, _>>();
 
+    if GROUP_MAPPINGS.is_empty() {
+        // No mappings configured: keep the legacy behavior of inviting into any org whose name
+        // exactly matches an IdP group, as `UserOrgType::User` with no collection overrides.
         for group in groups {
             if let Some(org) = Organization::find_by_name(group, conn).await {
                 if user_orgs.get(&org.uuid).is_none() {
-                    info!("Invitation to {} organization sent to {}", group, user.email);
+                    info!("Invitation to {} organization sent to {}", org.name, user.email);
                     organization_logic::invite(
                         user,
                         device,
                         ip,
                         &org,
                         UserOrgType::User,
-                        &org_groups,
+                        &Vec::new(),
                         true,
-                        &org_collections,
+                        &Vec::new(),
                         org.billing_email.clone(),
+                        // Tag the membership as SSO-granted so revocation never has to guess provenance.
+                        true,
                         conn,
                     )
                     .await?;
+                    summary.changes.push((EventType::OrganizationUserInvited, org.name));
                 }
             }
         }
+
+        return Ok(summary);
+    }
+
+    // Target org name -> the resolved grant from every mapping that matched one of the user's
+    // groups: the highest-privilege `user_type` among them, and the union of their collections
+    // (a user in both `engineering-readers` and `engineering-writers` keeps both grants, not just
+    // the one from whichever mapping happens to rank highest).
+    let wanted = resolve_wanted_grants(groups, &GROUP_MAPPINGS);
+
+    for (org_name, grant) in wanted.iter() {
+        let Some(org) = Organization::find_by_name(org_name, conn).await else {
+            warn!("SSO group mapping references unknown organization `{org_name}`");
+            continue;
+        };
+
+        let org_collections = resolve_collections(&org, &grant.collections, conn).await;
+
+        match user_orgs.remove(&org.uuid) {
+            None => {
+                info!("Invitation to {} organization sent to {}", org.name, user.email);
+                organization_logic::invite(
+                    user,
+                    device,
+                    ip,
+                    &org,
+                    grant.user_type,
+                    &Vec::new(),
+                    true,
+                    &org_collections,
+                    org.billing_email.clone(),
+                    // Tag the membership as SSO-granted so revocation never has to guess provenance.
+                    true,
+                    conn,
+                )
+                .await?;
+                summary.changes.push((EventType::OrganizationUserInvited, org.name));
+            }
+            Some(mut existing) => {
+                if existing.atype != grant.user_type as i32 {
+                    let was_privileged = org_type_rank(existing.atype) > org_type_rank(grant.user_type as i32);
+                    existing.atype = grant.user_type as i32;
+                    existing.save(conn).await?;
+                    summary.changes.push((EventType::OrganizationUserUpdated, org.name.clone()));
+                    debug!(
+                        "{} {} in {} organization via SSO group sync",
+                        if was_privileged { "Demoted" } else { "Promoted" },
+                        user.email,
+                        org.name
+                    );
+                }
+
+                organization_logic::sync_collections(user, &org, &org_collections, conn).await?;
+                user_orgs.insert(org.uuid.clone(), existing);
+            }
+        }
+    }
+
+    // Opt-in: memberships granted through SSO group sync are revoked once their backing group
+    // disappears from the token, so offboarding in the IdP actually offboards from Vaultwarden.
+    // Only memberships this sync itself created (`granted_by_sso`) are eligible -- a manually
+    // granted membership (e.g. an emergency Owner promotion) in an org that's otherwise
+    // SSO-managed must never be silently stripped because of a stale or misconfigured IdP claim.
+    if CONFIG.sso_organizations_revocation() {
+        let granted_orgs: Vec<String> =
+            GROUP_MAPPINGS.iter().map(|mapping| mapping.organization.clone()).collect();
+
+        for (org_uuid, user_org) in user_orgs {
+            if !user_org.granted_by_sso {
+                continue;
+            }
+
+            let Some(org) = Organization::find_by_uuid(&org_uuid, conn).await else {
+                continue;
+            };
+
+            if !granted_orgs.contains(&org.name) || wanted.contains_key(&org.name) {
+                continue;
+            }
+
+            // Never let a stale group claim remove the last Owner and orphan the organization --
+            // the same safety check the org-management routes enforce on manual removals.
+            let remaining_owners =
+                UserOrganization::count_confirmed_by_org_and_type(&org_uuid, UserOrgType::Owner as i32, conn).await;
+            if user_org.atype == UserOrgType::Owner as i32 && remaining_owners <= 1 {
+                warn!(
+                    "Refusing to revoke {}'s SSO-granted Owner membership in {} organization: they are the last Owner",
+                    user.email, org.name
+                );
+                continue;
+            }
+
+            info!("Revoking {} membership in {} organization, backing SSO group is gone", user.email, org.name);
+            user_org.delete(conn).await?;
+            summary.changes.push((EventType::OrganizationUserRemoved, org.name));
+        }
+    }
+
+    Ok(summary)
+}
+
+// Fast-path guard against a revoked SSO session being used to mint a new access token in the
+// narrow window between `handle_backchannel_logout` clearing the backing `Device` rows and that
+// write being visible to the connection serving the refresh request. The actual revocation is
+// persisted on the `Device` rows themselves (see `handle_backchannel_logout`), so this cache is
+// disposable: losing it on restart, or it not being shared in a multi-instance deployment, only
+// costs a tighter rejection window, not the revocation itself.
+static LOGOUT_REVOCATIONS: Lazy<Cache<String, ()>> =
+    Lazy::new(|| Cache::builder().max_capacity(10_000).time_to_live(Duration::from_secs(24 * 60 * 60)).build());
+
+fn is_revoked(idp_id: &str, sub: &str, sid: Option<&str>) -> bool {
+    if LOGOUT_REVOCATIONS.contains_key(&format!("{idp_id}\x1esub\x1e{sub}")) {
+        return true;
+    }
+
+    sid.is_some_and(|sid| LOGOUT_REVOCATIONS.contains_key(&format!("{idp_id}\x1esid\x1e{sid}")))
+}
+
+// Build the RP-Initiated Logout URL for the IdP backing `device`'s SSO session, if it advertises one.
+pub async fn logout_url(device: &Device, post_logout_redirect: &str) -> ApiResult<Option<Url>> {
+    if device.refresh_token.is_empty() {
+        return Ok(None);
+    }
+    let refresh_claims = auth::decode_refresh(&device.refresh_token)?;
+
+    let (Some(idp_id), Some(id_token)) = (refresh_claims.idp_id.as_deref(), refresh_claims.id_token.as_deref()) else {
+        return Ok(None);
+    };
+
+    let provider_client = cached_client(idp_id).await?;
+    let Some(mut end_session_url) = provider_client.end_session_endpoint else {
+        return Ok(None);
+    };
+
+    end_session_url
+        .query_pairs_mut()
+        .append_pair("id_token_hint", id_token)
+        .append_pair("post_logout_redirect_uri", post_logout_redirect)
+        .append_pair("state", &device.uuid);
+
+    Ok(Some(end_session_url))
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct LogoutTokenPayload {
+    sub: Option<String>,
+    sid: Option<String>,
+    #[serde(default)]
+    events: HashMap<String, serde_json::Value>,
+}
+
+const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+// Validate an IdP-pushed back-channel logout token (same JWKS/validation path as every other SSO
+// JWT) and revoke the sessions it names, so a global sign-out at the IdP ends the Vaultwarden side too.
+pub async fn handle_backchannel_logout(idp_id: &str, logout_token: &str, conn: &mut DbConn) -> ApiResult<()> {
+    let jwt_validation = decoding(idp_id)?;
+    let provider_client = cached_client(idp_id).await?;
+
+    let payload: LogoutTokenPayload =
+        jwt_validation.decode("logout_token", logout_token, Some(&provider_client.jwks_uri), true).await?;
+
+    if !payload.events.contains_key(BACKCHANNEL_LOGOUT_EVENT) {
+        err!("Logout token is missing the backchannel-logout event");
+    }
+
+    let Some(sub) = payload.sub else {
+        err!("Logout token is missing a `sub` claim");
+    };
+
+    LOGOUT_REVOCATIONS.insert(format!("{idp_id}\x1esub\x1e{sub}"), ());
+    if let Some(sid) = &payload.sid {
+        LOGOUT_REVOCATIONS.insert(format!("{idp_id}\x1esid\x1e{sid}"), ());
+    }
+
+    // Persist the revocation: clear the refresh_token on every Device tagged with this SSO
+    // subject/session so the session is actually dead, not just rejected by a cache entry that
+    // may not exist yet on another instance or after a restart.
+    let mut devices = Device::find_by_sso_sub(idp_id, &sub, conn).await;
+    if let Some(sid) = &payload.sid {
+        for device in Device::find_by_sso_sid(idp_id, sid, conn).await {
+            if !devices.iter().any(|d| d.uuid == device.uuid) {
+                devices.push(device);
+            }
+        }
+    }
+
+    for mut device in devices {
+        device.refresh_token = String::new();
+        device.save(conn).await?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection(external_id: &str) -> SsoCollectionMapping {
+        SsoCollectionMapping {
+            external_id: external_id.to_string(),
+            read_only: false,
+            hide_passwords: false,
+        }
+    }
+
+    fn mapping(
+        pattern: &str,
+        organization: &str,
+        user_type: UserOrgType,
+        collections: Vec<SsoCollectionMapping>,
+    ) -> CompiledGroupMapping {
+        CompiledGroupMapping {
+            regex: Regex::new(pattern).unwrap(),
+            organization: organization.to_string(),
+            user_type,
+            collections,
+        }
+    }
+
+    #[test]
+    fn org_type_rank_orders_by_privilege_not_discriminant() {
+        assert!(org_type_rank(UserOrgType::Owner as i32) > org_type_rank(UserOrgType::Admin as i32));
+        assert!(org_type_rank(UserOrgType::Admin as i32) > org_type_rank(UserOrgType::Manager as i32));
+        assert!(org_type_rank(UserOrgType::Manager as i32) > org_type_rank(UserOrgType::User as i32));
+        assert_eq!(org_type_rank(12345), -1);
+    }
+
+    #[test]
+    fn parse_user_org_type_accepts_known_roles_case_insensitively() {
+        assert!(matches!(parse_user_org_type("Owner"), Some(UserOrgType::Owner)));
+        assert!(matches!(parse_user_org_type("admin"), Some(UserOrgType::Admin)));
+        assert!(matches!(parse_user_org_type("MANAGER"), Some(UserOrgType::Manager)));
+        assert!(matches!(parse_user_org_type("user"), Some(UserOrgType::User)));
+        assert_eq!(parse_user_org_type("superuser"), None);
+    }
+
+    #[test]
+    fn resolve_wanted_grants_matches_a_single_mapping() {
+        let mappings = vec![mapping("^engineering-.*$", "Engineering", UserOrgType::User, vec![collection("readers")])];
+        let groups = vec!["engineering-readers".to_string()];
+
+        let wanted = resolve_wanted_grants(&groups, &mappings);
+
+        let grant = wanted.get("Engineering").expect("Engineering grant missing");
+        assert_eq!(grant.user_type as i32, UserOrgType::User as i32);
+        assert_eq!(grant.collections.len(), 1);
+        assert_eq!(grant.collections[0].external_id, "readers");
+    }
+
+    // Regression test for the bug fixed alongside this: a lower-ranked mapping processed first must
+    // not "stick" once a higher-ranked mapping for the same org also matches.
+    #[test]
+    fn resolve_wanted_grants_keeps_the_highest_rank_regardless_of_match_order() {
+        let mappings = vec![
+            mapping("^engineering-readers$", "Engineering", UserOrgType::User, vec![]),
+            mapping("^engineering-admins$", "Engineering", UserOrgType::Admin, vec![]),
+        ];
+        let groups = vec!["engineering-readers".to_string(), "engineering-admins".to_string()];
+
+        let wanted = resolve_wanted_grants(&groups, &mappings);
+
+        let grant = wanted.get("Engineering").expect("Engineering grant missing");
+        assert_eq!(grant.user_type as i32, UserOrgType::Admin as i32);
+    }
+
+    // Regression test for the collection-union bug: two mappings for the same org with different
+    // collections must both contribute, not just whichever mapping ranks highest.
+    #[test]
+    fn resolve_wanted_grants_unions_collections_across_matching_mappings() {
+        let mappings = vec![
+            mapping("^engineering-readers$", "Engineering", UserOrgType::User, vec![collection("readers")]),
+            mapping("^engineering-writers$", "Engineering", UserOrgType::Manager, vec![collection("writers")]),
+        ];
+        let groups = vec!["engineering-readers".to_string(), "engineering-writers".to_string()];
+
+        let wanted = resolve_wanted_grants(&groups, &mappings);
+
+        let grant = wanted.get("Engineering").expect("Engineering grant missing");
+        assert_eq!(grant.user_type as i32, UserOrgType::Manager as i32);
+        let mut external_ids: Vec<&str> = grant.collections.iter().map(|c| c.external_id.as_str()).collect();
+        external_ids.sort();
+        assert_eq!(external_ids, vec!["readers", "writers"]);
+    }
+
+    #[test]
+    fn resolve_wanted_grants_deduplicates_the_same_collection_from_multiple_mappings() {
+        let mappings = vec![
+            mapping("^engineering-readers$", "Engineering", UserOrgType::User, vec![collection("shared")]),
+            mapping("^engineering-writers$", "Engineering", UserOrgType::Manager, vec![collection("shared")]),
+        ];
+        let groups = vec!["engineering-readers".to_string(), "engineering-writers".to_string()];
+
+        let wanted = resolve_wanted_grants(&groups, &mappings);
+
+        let grant = wanted.get("Engineering").expect("Engineering grant missing");
+        assert_eq!(grant.collections.len(), 1);
+    }
+
+    #[test]
+    fn resolve_wanted_grants_ignores_non_matching_groups() {
+        let mappings = vec![mapping("^engineering-.*$", "Engineering", UserOrgType::User, vec![])];
+        let groups = vec!["marketing-readers".to_string()];
+
+        let wanted = resolve_wanted_grants(&groups, &mappings);
+
+        assert!(wanted.is_empty());
+    }
+}